@@ -1,18 +1,25 @@
 use crate::commands::command::RunnablePerItemContext;
+use crate::data::{Primitive, TaggedDictBuilder};
 use crate::errors::ShellError;
 use crate::parser::hir::SyntaxType;
 use crate::parser::registry::{CommandRegistry, Signature};
 use crate::prelude::*;
 use crate::utils::FileStructure;
-use std::path::PathBuf;
+use filetime::FileTime;
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
 
 pub struct Cpy;
 
 #[derive(Deserialize)]
 pub struct CopyArgs {
-    source: Tagged<PathBuf>,
-    destination: Tagged<PathBuf>,
+    #[serde(rename = "source")]
+    sources: Vec<Tagged<PathBuf>>,
     recursive: Tagged<bool>,
+    verbose: Tagged<bool>,
+    preserve: Tagged<bool>,
+    #[serde(rename = "target")]
+    target_directory: Option<Tagged<PathBuf>>,
 }
 
 impl PerItemCommand for Cpy {
@@ -32,24 +39,197 @@ impl PerItemCommand for Cpy {
 
     fn signature(&self) -> Signature {
         Signature::build("cp")
-            .required("source", SyntaxType::Path)
-            .required("destination", SyntaxType::Path)
+            .rest("source", SyntaxType::Path)
             .named("file", SyntaxType::Any)
+            .named("target", SyntaxType::Path)
             .switch("recursive")
+            .switch("verbose")
+            .switch("preserve")
     }
 }
 
+// Displays `path` relative to `cwd` when it lives underneath it, falling back to
+// the absolute path otherwise. Keeps diagnostics and verbose output readable
+// instead of leaking a fully `dunce::canonicalize`d filesystem layout.
+fn relative_to_cwd(cwd: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(cwd)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn copied_row(cwd: &Path, name_span: Span, from: &Path, to: &Path) -> ReturnValue {
+    let mut dict = TaggedDictBuilder::new(name_span);
+    dict.insert(
+        "from",
+        Value::Primitive(Primitive::Path(relative_to_cwd(cwd, from))),
+    );
+    dict.insert(
+        "to",
+        Value::Primitive(Primitive::Path(relative_to_cwd(cwd, to))),
+    );
+    ReturnSuccess::value(dict.into_tagged_value())
+}
+
+fn preserve_metadata(name_span: Span, src: &Path, dst: &Path) -> Result<(), ShellError> {
+    let metadata = match std::fs::metadata(src) {
+        Err(e) => return Err(ShellError::labeled_error(e.to_string(), e.to_string(), name_span)),
+        Ok(o) => o,
+    };
+
+    match std::fs::set_permissions(dst, metadata.permissions()) {
+        Err(e) => return Err(ShellError::labeled_error(e.to_string(), e.to_string(), name_span)),
+        Ok(o) => o,
+    };
+
+    let atime = FileTime::from_last_access_time(&metadata);
+    let mtime = FileTime::from_last_modification_time(&metadata);
+
+    match filetime::set_file_times(dst, atime, mtime) {
+        Err(e) => Err(ShellError::labeled_error(e.to_string(), e.to_string(), name_span)),
+        Ok(o) => Ok(o),
+    }
+}
+
+fn copy_tree(
+    cwd: &Path,
+    name_span: Span,
+    entries: Vec<(PathBuf, PathBuf)>,
+    preserve: bool,
+    verbose: bool,
+    results: &mut VecDeque<ReturnValue>,
+) -> Result<(), ShellError> {
+    for (src, dst) in entries.iter().filter(|(src, _)| src.is_dir()) {
+        if !dst.exists() {
+            match std::fs::create_dir_all(dst) {
+                Err(e) => {
+                    return Err(ShellError::labeled_error(e.to_string(), e.to_string(), name_span));
+                }
+                Ok(o) => o,
+            };
+
+            if verbose {
+                results.push_back(copied_row(cwd, name_span, src, dst));
+            }
+        }
+    }
+
+    let copy_results: Result<Vec<Option<ReturnValue>>, ShellError> = entries
+        .par_iter()
+        .filter(|(src, _)| !src.is_dir())
+        .map(|(src, dst)| {
+            std::fs::copy(src, dst)
+                .map_err(|e| ShellError::labeled_error(e.to_string(), e.to_string(), name_span))?;
+
+            if preserve {
+                preserve_metadata(name_span, src, dst)?;
+            }
+
+            Ok(if verbose {
+                Some(copied_row(cwd, name_span, src, dst))
+            } else {
+                None
+            })
+        })
+        .collect();
+
+    for row in copy_results?.into_iter().flatten() {
+        results.push_back(row);
+    }
+
+    // Directories are only stamped with their preserved metadata once every
+    // file and subdirectory has been created underneath them, deepest first,
+    // since populating a directory updates its mtime and would otherwise
+    // clobber the value we just restored.
+    if preserve {
+        let mut dirs: Vec<&(PathBuf, PathBuf)> =
+            entries.iter().filter(|(src, _)| src.is_dir()).collect();
+        dirs.sort_by_key(|(_, dst)| std::cmp::Reverse(dst.components().count()));
+
+        for (src, dst) in dirs {
+            preserve_metadata(name_span, src, dst)?;
+        }
+    }
+
+    Ok(())
+}
+
 pub fn cp(
     args: CopyArgs,
     context: &RunnablePerItemContext,
 ) -> Result<VecDeque<ReturnValue>, ShellError> {
-    let mut source = PathBuf::from(context.shell_manager.path());
-    let mut destination = PathBuf::from(context.shell_manager.path());
+    let cwd = PathBuf::from(context.shell_manager.path());
+    let cwd = dunce::canonicalize(&cwd).unwrap_or(cwd);
     let name_span = context.name;
+    let mut results = VecDeque::new();
 
-    source.push(&args.source.item);
+    let (source_tags, destination, destination_span): (&[Tagged<PathBuf>], PathBuf, Span) =
+        if let Some(target_directory) = &args.target_directory {
+            let mut path = cwd.clone();
+            path.push(&target_directory.item);
 
-    destination.push(&args.destination.item);
+            if !path.is_dir() {
+                return Err(ShellError::labeled_error(
+                    format!(
+                        "{} is not a directory",
+                        relative_to_cwd(&cwd, &path).display()
+                    ),
+                    "target is not a directory",
+                    target_directory.tag,
+                ));
+            }
+
+            (&args.sources[..], path, target_directory.span())
+        } else if let Some((destination_tag, source_tags)) = args.sources.split_last() {
+            let mut path = cwd.clone();
+            path.push(&destination_tag.item);
+            (source_tags, path, destination_tag.span())
+        } else {
+            return Err(ShellError::labeled_error(
+                "cp requires a destination or --target",
+                "cp requires a destination or --target",
+                name_span,
+            ));
+        };
+
+    if source_tags.is_empty() {
+        return Err(ShellError::labeled_error(
+            "cp requires at least one source",
+            "cp requires at least one source",
+            name_span,
+        ));
+    }
+
+    for source_tag in source_tags {
+        copy_source(
+            &cwd,
+            name_span,
+            source_tag,
+            &destination,
+            destination_span,
+            args.recursive.item,
+            args.verbose.item,
+            args.preserve.item,
+            &mut results,
+        )?;
+    }
+
+    Ok(results)
+}
+
+fn copy_source(
+    cwd: &Path,
+    name_span: Span,
+    source_tag: &Tagged<PathBuf>,
+    destination: &Path,
+    destination_span: Span,
+    recursive: bool,
+    verbose: bool,
+    preserve: bool,
+    results: &mut VecDeque<ReturnValue>,
+) -> Result<(), ShellError> {
+    let mut source = cwd.to_path_buf();
+    source.push(&source_tag.item);
+    let mut destination = destination.to_path_buf();
 
     let sources = glob::glob(&source.to_string_lossy());
 
@@ -57,7 +237,7 @@ pub fn cp(
         return Err(ShellError::labeled_error(
             "Invalid pattern.",
             "Invalid pattern.",
-            args.source.tag,
+            source_tag.tag,
         ));
     }
 
@@ -65,11 +245,11 @@ pub fn cp(
 
     if sources.len() == 1 {
         if let Ok(entry) = &sources[0] {
-            if entry.is_dir() && !args.recursive.item {
+            if entry.is_dir() && !recursive {
                 return Err(ShellError::labeled_error(
                     "is a directory (not copied). Try using \"--recursive\".",
                     "is a directory (not copied). Try using \"--recursive\".",
-                    args.source.tag,
+                    source_tag.tag,
                 ));
             }
 
@@ -100,6 +280,14 @@ pub fn cp(
                             }
                             Ok(o) => o,
                         };
+
+                        if preserve {
+                            preserve_metadata(name_span, src, dst)?;
+                        }
+
+                        if verbose {
+                            results.push_back(copied_row(cwd, name_span, src, dst));
+                        }
                     }
                 }
             }
@@ -117,6 +305,10 @@ pub fn cp(
                         Ok(o) => o,
                     };
 
+                    if verbose {
+                        results.push_back(copied_row(cwd, name_span, entry, &destination));
+                    }
+
                     let strategy = |(source_file, depth_level)| {
                         let mut new_dst = destination.clone();
                         let path = dunce::canonicalize(&source_file).unwrap();
@@ -137,34 +329,15 @@ pub fn cp(
                         (PathBuf::from(&source_file), PathBuf::from(new_dst))
                     };
 
-                    for (ref src, ref dst) in sources.paths_applying_with(strategy) {
-                        if src.is_dir() {
-                            if !dst.exists() {
-                                match std::fs::create_dir_all(dst) {
-                                    Err(e) => {
-                                        return Err(ShellError::labeled_error(
-                                            e.to_string(),
-                                            e.to_string(),
-                                            name_span,
-                                        ));
-                                    }
-                                    Ok(o) => o,
-                                };
-                            }
-                        }
+                    let entries: Vec<(PathBuf, PathBuf)> =
+                        sources.paths_applying_with(strategy).collect();
 
-                        if src.is_file() {
-                            match std::fs::copy(src, dst) {
-                                Err(e) => {
-                                    return Err(ShellError::labeled_error(
-                                        e.to_string(),
-                                        e.to_string(),
-                                        name_span,
-                                    ));
-                                }
-                                Ok(o) => o,
-                            };
-                        }
+                    copy_tree(cwd, name_span, entries, preserve, verbose, results)?;
+
+                    // Restore the top-level directory's own metadata only after its
+                    // contents have been copied, or the copies would clobber its mtime.
+                    if preserve {
+                        preserve_metadata(name_span, entry, &destination)?;
                     }
                 } else {
                     destination.push(entry.file_name().unwrap());
@@ -180,6 +353,10 @@ pub fn cp(
                         Ok(o) => o,
                     };
 
+                    if verbose {
+                        results.push_back(copied_row(cwd, name_span, entry, &destination));
+                    }
+
                     let strategy = |(source_file, depth_level)| {
                         let mut new_dst = dunce::canonicalize(&destination).unwrap();
                         let path = dunce::canonicalize(&source_file).unwrap();
@@ -200,45 +377,24 @@ pub fn cp(
                         (PathBuf::from(&source_file), PathBuf::from(new_dst))
                     };
 
-                    for (ref src, ref dst) in sources.paths_applying_with(strategy) {
-                        if src.is_dir() {
-                            if !dst.exists() {
-                                match std::fs::create_dir_all(dst) {
-                                    Err(e) => {
-                                        return Err(ShellError::labeled_error(
-                                            e.to_string(),
-                                            e.to_string(),
-                                            name_span,
-                                        ));
-                                    }
-                                    Ok(o) => o,
-                                };
-                            }
-                        }
+                    let entries: Vec<(PathBuf, PathBuf)> =
+                        sources.paths_applying_with(strategy).collect();
 
-                        if src.is_file() {
-                            match std::fs::copy(src, dst) {
-                                Err(e) => {
-                                    return Err(ShellError::labeled_error(
-                                        e.to_string(),
-                                        e.to_string(),
-                                        name_span,
-                                    ));
-                                }
-                                Ok(o) => o,
-                            };
-                        }
+                    copy_tree(cwd, name_span, entries, preserve, verbose, results)?;
+
+                    if preserve {
+                        preserve_metadata(name_span, entry, &destination)?;
                     }
                 }
             }
         }
     } else {
         if destination.exists() {
-            if !sources.iter().all(|x| (x.as_ref().unwrap()).is_file()) {
+            if !sources.iter().all(|x| (x.as_ref().unwrap()).is_file()) && !recursive {
                 return Err(ShellError::labeled_error(
                     "Copy aborted (directories found). Recursive copying in patterns not supported yet (try copying the directory directly)",
                     "Copy aborted (directories found). Recursive copying in patterns not supported yet (try copying the directory directly)",
-                    args.source.tag,
+                    source_tag.tag,
                 ));
             }
 
@@ -253,11 +409,66 @@ pub fn cp(
                                 return Err(ShellError::labeled_error(
                                     e.to_string(),
                                     e.to_string(),
-                                    args.source.tag,
+                                    source_tag.tag,
                                 ));
                             }
                             Ok(o) => o,
                         };
+
+                        if preserve {
+                            preserve_metadata(name_span, &entry, &to)?;
+                        }
+
+                        if verbose {
+                            results.push_back(copied_row(cwd, name_span, &entry, &to));
+                        }
+                    } else if entry.is_dir() && recursive {
+                        match std::fs::create_dir_all(&to) {
+                            Err(e) => {
+                                return Err(ShellError::labeled_error(
+                                    e.to_string(),
+                                    e.to_string(),
+                                    name_span,
+                                ));
+                            }
+                            Ok(o) => o,
+                        };
+
+                        if verbose {
+                            results.push_back(copied_row(cwd, name_span, &entry, &to));
+                        }
+
+                        let mut sources: FileStructure = FileStructure::new();
+                        sources.walk_decorate(&entry);
+
+                        let strategy = |(source_file, depth_level)| {
+                            let mut new_dst = dunce::canonicalize(&to).unwrap();
+                            let path = dunce::canonicalize(&source_file).unwrap();
+
+                            let mut comps: Vec<_> = path
+                                .components()
+                                .map(|fragment| fragment.as_os_str())
+                                .rev()
+                                .take(1 + depth_level)
+                                .collect();
+
+                            comps.reverse();
+
+                            for fragment in comps.iter() {
+                                new_dst.push(fragment);
+                            }
+
+                            (PathBuf::from(&source_file), PathBuf::from(new_dst))
+                        };
+
+                        let entries: Vec<(PathBuf, PathBuf)> =
+                            sources.paths_applying_with(strategy).collect();
+
+                        copy_tree(cwd, name_span, entries, preserve, verbose, results)?;
+
+                        if preserve {
+                            preserve_metadata(name_span, &entry, &to)?;
+                        }
                     }
                 }
             }
@@ -265,16 +476,117 @@ pub fn cp(
             return Err(ShellError::labeled_error(
                 format!(
                     "Copy aborted. (Does {:?} exist?)",
-                    &destination.file_name().unwrap()
+                    relative_to_cwd(cwd, &destination)
                 ),
                 format!(
                     "Copy aborted. (Does {:?} exist?)",
-                    &destination.file_name().unwrap()
+                    relative_to_cwd(cwd, &destination)
                 ),
-                args.destination.span(),
+                destination_span,
             ));
         }
     }
 
-    Ok(VecDeque::new())
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("nu_cp_test_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn relative_to_cwd_strips_the_prefix() {
+        let cwd = PathBuf::from("/home/user/project");
+        let path = PathBuf::from("/home/user/project/src/cp.rs");
+
+        assert_eq!(relative_to_cwd(&cwd, &path), PathBuf::from("src/cp.rs"));
+    }
+
+    #[test]
+    fn relative_to_cwd_falls_back_to_the_absolute_path_outside_cwd() {
+        let cwd = PathBuf::from("/home/user/project");
+        let path = PathBuf::from("/etc/hosts");
+
+        assert_eq!(relative_to_cwd(&cwd, &path), path);
+    }
+
+    #[test]
+    fn copy_tree_copies_nested_files_and_directories() {
+        let root = scratch_dir("tree");
+        let src_dir = root.join("src");
+        let dst_dir = root.join("dst");
+        std::fs::create_dir_all(src_dir.join("nested")).unwrap();
+        std::fs::write(src_dir.join("nested").join("a.txt"), b"hello").unwrap();
+
+        let entries = vec![
+            (src_dir.join("nested"), dst_dir.join("nested")),
+            (
+                src_dir.join("nested").join("a.txt"),
+                dst_dir.join("nested").join("a.txt"),
+            ),
+        ];
+
+        let mut results = VecDeque::new();
+        copy_tree(&root, Span::unknown(), entries, false, false, &mut results).unwrap();
+
+        assert_eq!(
+            std::fs::read(dst_dir.join("nested").join("a.txt")).unwrap(),
+            b"hello"
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn copy_tree_restores_directory_metadata_after_its_children_are_copied() {
+        let root = scratch_dir("preserve_order");
+        let src_dir = root.join("src");
+        let dst_dir = root.join("dst");
+        std::fs::create_dir_all(&src_dir).unwrap();
+        std::fs::write(src_dir.join("a.txt"), b"hello").unwrap();
+
+        let old_mtime = FileTime::from_unix_time(1_000_000, 0);
+        filetime::set_file_mtime(&src_dir, old_mtime).unwrap();
+
+        let entries = vec![
+            (src_dir.clone(), dst_dir.clone()),
+            (src_dir.join("a.txt"), dst_dir.join("a.txt")),
+        ];
+
+        let mut results = VecDeque::new();
+        copy_tree(&root, Span::unknown(), entries, true, false, &mut results).unwrap();
+
+        let restored_mtime =
+            FileTime::from_last_modification_time(&std::fs::metadata(&dst_dir).unwrap());
+        assert_eq!(restored_mtime, old_mtime);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn copy_tree_surfaces_the_first_copy_error() {
+        let root = scratch_dir("fail_fast");
+        let dst_dir = root.join("dst");
+        std::fs::create_dir_all(&dst_dir).unwrap();
+
+        let entries = vec![(
+            root.join("does_not_exist.txt"),
+            dst_dir.join("does_not_exist.txt"),
+        )];
+
+        let mut results = VecDeque::new();
+        let result = copy_tree(&root, Span::unknown(), entries, false, false, &mut results);
+        assert!(result.is_err());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
 }